@@ -0,0 +1,204 @@
+// wl-clipboard-rs only talks to a Wayland compositor; ClipboardProvider lets
+// history recording and launcher paste-back run the same way against X11
+// (xclip/xsel) or anything else reachable as a command. When WAYLAND_DISPLAY
+// is set, main keeps using wl-clipboard-rs directly for both and the
+// provider is not used for them.
+
+use async_trait::async_trait;
+use std::process::Stdio;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use toml::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    Clipboard,
+    Primary,
+}
+
+#[async_trait]
+pub trait ClipboardProvider: Send + Sync {
+    fn name(&self) -> &str;
+    async fn get_contents(&self, selection: Selection) -> std::io::Result<Vec<u8>>;
+    async fn set_contents(&self, bytes: Vec<u8>, selection: Selection) -> std::io::Result<()>;
+}
+
+// Backed by external commands, e.g. wl-copy/wl-paste or xclip/xsel. Each
+// call spawns the configured command fresh; set_contents pipes bytes
+// through its stdin, get_contents captures its stdout.
+pub struct CommandProvider {
+    name: String,
+    copy: Vec<String>,
+    paste: Vec<String>,
+    primary_copy: Vec<String>,
+    primary_paste: Vec<String>,
+}
+
+impl CommandProvider {
+    pub fn new(
+        name: impl Into<String>,
+        copy: Vec<String>,
+        paste: Vec<String>,
+        primary_copy: Vec<String>,
+        primary_paste: Vec<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            copy,
+            paste,
+            primary_copy,
+            primary_paste,
+        }
+    }
+
+    fn argv(&self, selection: Selection, writing: bool) -> &[String] {
+        match (selection, writing) {
+            (Selection::Clipboard, true) => &self.copy,
+            (Selection::Clipboard, false) => &self.paste,
+            (Selection::Primary, true) => &self.primary_copy,
+            (Selection::Primary, false) => &self.primary_paste,
+        }
+    }
+}
+
+#[async_trait]
+impl ClipboardProvider for CommandProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn get_contents(&self, selection: Selection) -> std::io::Result<Vec<u8>> {
+        let Some((program, args)) = self.argv(selection, false).split_first() else {
+            return Ok(Vec::new());
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let mut out = Vec::new();
+        if let Some(mut stdout) = child.stdout.take() {
+            stdout.read_to_end(&mut out).await?;
+        }
+        child.wait().await?;
+        Ok(out)
+    }
+
+    async fn set_contents(&self, bytes: Vec<u8>, selection: Selection) -> std::io::Result<()> {
+        let Some((program, args)) = self.argv(selection, true).split_first() else {
+            return Ok(());
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&bytes).await?;
+        }
+        child.wait().await?;
+        Ok(())
+    }
+}
+
+// Tried in priority order when config.toml doesn't configure [clipboard]
+// explicitly. Mirrors Helix's detection: Wayland tools first if
+// WAYLAND_DISPLAY is set, then X11 tools if DISPLAY is set.
+fn builtin_candidates() -> Vec<CommandProvider> {
+    let mut candidates = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        candidates.push(CommandProvider::new(
+            "wl-clipboard",
+            vec!["wl-copy".into()],
+            vec!["wl-paste".into(), "-n".into()],
+            vec!["wl-copy".into(), "--primary".into()],
+            vec!["wl-paste".into(), "-n".into(), "--primary".into()],
+        ));
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        candidates.push(CommandProvider::new(
+            "xclip",
+            vec!["xclip".into(), "-selection".into(), "clipboard".into()],
+            vec![
+                "xclip".into(),
+                "-selection".into(),
+                "clipboard".into(),
+                "-o".into(),
+            ],
+            vec!["xclip".into(), "-selection".into(), "primary".into()],
+            vec![
+                "xclip".into(),
+                "-selection".into(),
+                "primary".into(),
+                "-o".into(),
+            ],
+        ));
+        candidates.push(CommandProvider::new(
+            "xsel",
+            vec!["xsel".into(), "--clipboard".into(), "--input".into()],
+            vec!["xsel".into(), "--clipboard".into(), "--output".into()],
+            vec!["xsel".into(), "--primary".into(), "--input".into()],
+            vec!["xsel".into(), "--primary".into(), "--output".into()],
+        ));
+    }
+    candidates
+}
+
+fn in_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+// An explicit [clipboard] table in config.toml always wins; otherwise probe
+// WAYLAND_DISPLAY/DISPLAY and fall back through builtin_candidates.
+pub fn detect_provider(config: &Value) -> CommandProvider {
+    if let Some(table) = config.get("clipboard").and_then(Value::as_table) {
+        let array_of_strings = |key: &str| -> Vec<String> {
+            table
+                .get(key)
+                .and_then(Value::as_array)
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let copy = array_of_strings("copy");
+        let paste = array_of_strings("paste");
+        if !copy.is_empty() || !paste.is_empty() {
+            let primary_copy = array_of_strings("primary_copy");
+            let primary_copy = if primary_copy.is_empty() {
+                copy.clone()
+            } else {
+                primary_copy
+            };
+            let primary_paste = array_of_strings("primary_paste");
+            let primary_paste = if primary_paste.is_empty() {
+                paste.clone()
+            } else {
+                primary_paste
+            };
+            return CommandProvider::new("configured", copy, paste, primary_copy, primary_paste);
+        }
+    }
+
+    for candidate in builtin_candidates() {
+        if [&candidate.copy, &candidate.paste]
+            .iter()
+            .all(|argv| argv.first().is_some_and(|program| in_path(program)))
+        {
+            return candidate;
+        }
+    }
+
+    // Nothing detected (e.g. headless with no WAYLAND_DISPLAY/DISPLAY at
+    // all): keep clapboard's historical default so error messages still
+    // name the tool a Wayland user is expected to install.
+    CommandProvider::new(
+        "wl-clipboard",
+        vec!["wl-copy".into()],
+        vec!["wl-paste".into(), "-n".into()],
+        vec!["wl-copy".into(), "--primary".into()],
+        vec!["wl-paste".into(), "-n".into(), "--primary".into()],
+    )
+}
@@ -0,0 +1,100 @@
+// Entries live under cache_dir in a directory named after the blake3 digest
+// of their mime bytes, so copying the same thing twice just bumps recency
+// instead of growing the history. Recency can't be read off the directory
+// name anymore, so each entry keeps a .mtime marker with its last-copied
+// timestamp; list_entries/clean_history sort by that.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs;
+
+const MTIME_MARKER: &str = ".mtime";
+const TEXT_FILES: [&str; 5] = ["UTF8_STRING", "TEXT", "text.plain", "text.html", "STRING"];
+
+pub async fn read_text(dir: &Path) -> Option<String> {
+    for file_name in TEXT_FILES {
+        if let Ok(content) = fs::read_to_string(dir.join(file_name)).await {
+            return Some(content);
+        }
+    }
+    None
+}
+
+// Mimes are hashed in sorted order so the same content always lands on the
+// same digest regardless of enumeration order.
+pub fn digest(mimes: &[(String, Vec<u8>)]) -> String {
+    let mut sorted: Vec<&(String, Vec<u8>)> = mimes.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut hasher = blake3::Hasher::new();
+    for (mime, bytes) in sorted {
+        hasher.update(mime.as_bytes());
+        hasher.update(bytes);
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+// If an entry with the same digest already exists, only its recency marker
+// is bumped. Returns the entry's directory name (its digest).
+pub async fn store_event(cache_dir: &Path, mimes: Vec<(String, Vec<u8>)>) -> io::Result<String> {
+    let id = digest(&mimes);
+    let dir = cache_dir.join(&id);
+
+    if fs::metadata(&dir).await.is_err() {
+        fs::create_dir_all(&dir).await?;
+        for (mime, bytes) in &mimes {
+            let file_path = dir.join(mime.replace('/', "."));
+            fs::write(&file_path, bytes).await?;
+        }
+    }
+    touch(&dir).await?;
+    Ok(id)
+}
+
+async fn touch(dir: &Path) -> io::Result<()> {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    fs::write(dir.join(MTIME_MARKER), millis.to_string()).await
+}
+
+async fn recency(dir: &Path) -> u128 {
+    fs::read_to_string(dir.join(MTIME_MARKER))
+        .await
+        .ok()
+        .and_then(|contents| contents.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+// Newest-first, skipping dot-directories.
+pub async fn list_entries(cache_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Ok(mut read_dir) = fs::read_dir(cache_dir).await {
+        while let Ok(Some(entry)) = read_dir.next_entry().await {
+            let path = entry.path();
+            if path.is_dir() && !entry.file_name().to_string_lossy().starts_with('.') {
+                dirs.push(path);
+            }
+        }
+    }
+
+    let mut with_recency = Vec::with_capacity(dirs.len());
+    for dir in dirs {
+        let r = recency(&dir).await;
+        with_recency.push((r, dir));
+    }
+    with_recency.sort_by(|a, b| b.0.cmp(&a.0));
+    with_recency.into_iter().map(|(_, dir)| dir).collect()
+}
+
+// Remove history entries beyond max, keeping the newest.
+pub async fn clean_history(cache_dir: &Path, max: usize) -> io::Result<()> {
+    for (index, dir) in list_entries(cache_dir).await.into_iter().enumerate() {
+        if index >= max {
+            fs::remove_dir_all(&dir).await?;
+        }
+    }
+    Ok(())
+}
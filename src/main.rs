@@ -1,11 +1,16 @@
+mod clipboard;
+mod history;
+mod manage;
+mod osc52;
+mod thumbnail;
+
 use clap::Parser;
+use clipboard::{ClipboardProvider, Selection};
 use indexmap::IndexMap;
-use std::path::Path;
+use std::io::Read;
 use std::path::PathBuf;
-use std::{
-    io,
-    time::{SystemTime, UNIX_EPOCH},
-};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
@@ -17,13 +22,21 @@ use wl_clipboard_rs::copy::{MimeSource, MimeType, Options, Source};
 use wl_clipboard_rs::paste::{get_contents, ClipboardType, Seat};
 use xdg::BaseDirectories;
 
-/// Clapboard, a clipboard manager for Wayland
+/// Clapboard, a clipboard manager for Wayland and X11
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
     /// Record mode, choose between "primary", "clipboard", or the default "both"
     #[arg(short, long, num_args(0..=1), default_missing_value = "both")]
     record: Option<String>,
+
+    /// Show a management menu (paste / delete / pin to favorites) for the selected history entry; a selected favorite is pasted directly
+    #[arg(long)]
+    manage: bool,
+
+    /// Set the clipboard via an OSC52 escape sequence instead of a local clipboard command
+    #[arg(long)]
+    osc52: bool,
 }
 
 #[tokio::main]
@@ -35,7 +48,7 @@ async fn main() {
         .place_config_file("config.toml")
         .expect("cannot create configuration directory");
 
-    let toml_string = fs::read_to_string(config_path)
+    let toml_string = fs::read_to_string(&config_path)
         .await
         .unwrap_or(String::from(""));
     let value: Value = toml::from_str(&toml_string).unwrap();
@@ -57,18 +70,33 @@ async fn main() {
         .and_then(|v| v.as_integer())
         .unwrap_or(50) as usize;
 
+    let osc52_max_size = value
+        .get("osc52_max_size")
+        .and_then(|v| v.as_integer())
+        .map(|size| size as usize)
+        .unwrap_or(osc52::DEFAULT_MAX_SIZE);
+
+    // Off by default: decoding every image entry isn't free.
+    let thumbnails_enabled = value
+        .get("thumbnails")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
     let default_favorites_value = Value::Table(toml::value::Table::new());
-    let favorites = value
+    let favorites: toml::value::Table = value
         .get("favorites")
-        .unwrap_or_else(|| &default_favorites_value)
+        .unwrap_or(&default_favorites_value)
         .as_table()
-        .unwrap();
+        .unwrap()
+        .clone();
 
     let cache_dir = xdg_dirs.get_cache_home();
+    let on_wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let provider: Arc<dyn ClipboardProvider> = Arc::new(clipboard::detect_provider(&value));
 
     match args.record {
         Some(record) => {
-            println!("Clapboard recording {record}...");
+            println!("Clapboard recording {record} via {}...", provider.name());
             let listeners = match record.as_str() {
                 "primary" => vec!["primary"],
                 "clipboard" => vec!["clipboard"],
@@ -80,11 +108,25 @@ async fn main() {
             let tasks: Vec<_> = listeners
                 .iter()
                 .map(|&paste_type| {
-                    task::spawn(listen_to_clipboard(
-                        paste_type,
-                        cache_dir.clone(),
-                        history_size,
-                    ))
+                    if on_wayland {
+                        task::spawn(listen_to_clipboard(
+                            paste_type,
+                            cache_dir.clone(),
+                            history_size,
+                        ))
+                    } else {
+                        let selection = if paste_type == "primary" {
+                            Selection::Primary
+                        } else {
+                            Selection::Clipboard
+                        };
+                        task::spawn(poll_clipboard(
+                            provider.clone(),
+                            selection,
+                            cache_dir.clone(),
+                            history_size,
+                        ))
+                    }
                 })
                 .collect();
 
@@ -94,133 +136,256 @@ async fn main() {
             }
         }
         None => {
-            let mut data: IndexMap<String, String> = IndexMap::new();
+            // Keyed by content digest, not the truncated display text
+            let mut entries: IndexMap<String, String> = IndexMap::new();
+            // Display label -> digest (or favorite key)
+            let mut display_to_id: IndexMap<String, String> = IndexMap::new();
+            // Cached preview PNGs for image entries, keyed by digest
+            let mut thumbnails: IndexMap<String, PathBuf> = IndexMap::new();
 
-            let mut entries = vec![];
-            if let Ok(mut read_dir) = fs::read_dir(&cache_dir).await {
-                while let Ok(Some(entry)) = read_dir.next_entry().await {
-                    entries.push(entry);
+            for dir in history::list_entries(&cache_dir).await {
+                let id = dir
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                let label = match history::read_text(&dir).await {
+                    Some(content) => content
+                        .trim()
+                        .to_string()
+                        .replace("\n", " ")
+                        .replace("\0", "")
+                        .chars()
+                        .take(50) // Avoid long text
+                        .collect(),
+                    None => {
+                        let image = if thumbnails_enabled {
+                            thumbnail::describe_and_cache(&dir).await
+                        } else {
+                            None
+                        };
+                        match image {
+                            Some((description, thumb_path)) => {
+                                thumbnails.insert(id.clone(), thumb_path);
+                                description
+                            }
+                            None => {
+                                println!("No textfile found for: {}", id);
+                                id.clone()
+                            }
+                        }
+                    }
+                };
+                entries.entry(id).or_insert(label);
+            }
+            for (id, label) in &entries {
+                display_to_id
+                    .entry(label.clone())
+                    .or_insert_with(|| id.clone());
+            }
+            for (key, _) in &favorites {
+                display_to_id
+                    .entry(key.clone())
+                    .or_insert_with(|| key.clone());
+            }
+
+            // Icon-capable launchers (rofi, wofi, tofi) read icons off a
+            // label\0icon\x1f/path line and still return just the label.
+            let input = display_to_id
+                .iter()
+                .map(|(label, id)| match thumbnails.get(id) {
+                    Some(thumb) => format!("{label}\0icon\x1f{}", thumb.display()),
+                    None => label.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let launcher_argv = launcher.unwrap();
+
+            let Some(result) = run_launcher(launcher_argv, &input).await else {
+                return;
+            };
+
+            if favorites.contains_key(&result) {
+                let bytes = favorites
+                    .get(&result)
+                    .unwrap()
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+                    .into_bytes();
+                if !(args.osc52 && send_osc52(&bytes, osc52_max_size)) {
+                    if on_wayland {
+                        let mut opts = Options::new();
+                        opts.foreground(true); // We need to keep the process alive for pasting to work
+                        opts.copy(Source::Bytes(bytes.into_boxed_slice()), MimeType::Autodetect)
+                            .expect("Failed to copy to clipboard");
+                    } else {
+                        provider
+                            .set_contents(bytes, Selection::Clipboard)
+                            .await
+                            .unwrap_or_else(|err| {
+                                panic!("Failed to copy to clipboard via {}: {}", provider.name(), err)
+                            });
+                    }
                 }
+                return;
             }
 
-            // Sort entries by file name (ascending order)
-            entries.sort_by(|a, b| b.file_name().cmp(&a.file_name()));
+            let Some(id) = display_to_id.get(&result).cloned() else {
+                return;
+            };
 
-            // Iterate over sorted entries
-            for entry in entries {
-                if entry.path().is_dir() {
-                    let timestamp = entry.file_name().into_string().unwrap_or_default();
-                    if timestamp.starts_with(".") {
-                        continue;
+            if args.manage {
+                let action = run_launcher(launcher_argv, "Paste\nDelete\nPin to favorites").await;
+                match action.as_deref() {
+                    Some("Delete") => {
+                        if let Err(err) = manage::delete_entry(&cache_dir, &id).await {
+                            eprintln!("Failed to delete entry {}: {}", id, err);
+                        }
+                        return;
                     }
-                    let text_files =
-                        vec!["UTF8_STRING", "TEXT", "text.plain", "text.html", "STRING"];
-                    let mut found_file = false;
-                    let mut content = String::new();
-                    for file_name in text_files {
-                        let textual_representation = entry.path().join(file_name);
-
-                        if fs::metadata(&textual_representation).await.is_ok() {
-                            if let Ok(read_content) = fs::read_to_string(&textual_representation).await {
-                                content = read_content;
-                                found_file = true;
-                                break;
+                    Some("Pin to favorites") => {
+                        let Some(text) = history::read_text(&cache_dir.join(&id)).await else {
+                            eprintln!("Entry {} has no text representation, refusing to pin its digest as a favorite", id);
+                            return;
+                        };
+                        if let Some(key) = run_launcher(launcher_argv, "").await {
+                            if let Err(err) = manage::pin_favorite(&config_path, &key, &text).await
+                            {
+                                eprintln!("Failed to update {}: {}", config_path.display(), err);
                             }
                         }
+                        return;
                     }
-                    if found_file {
-                        data.insert(
-                            content
-                                .trim()
-                                .to_string()
-                                .replace("\n", " ")
-                                .replace("\0", "")
-                                .chars()
-                                .take(50) // Avoid long text
-                                .collect(),
-                            timestamp.to_string(),
-                        );
-                    } else {
-                        // If no file was found, proceed with the else logic
-                        println!("No textfile found for: {}", timestamp.to_string());
-                        data.entry(timestamp.to_string())
-                            .or_insert_with(|| timestamp.to_string());
-                    }
+                    _ => {} // "Paste" (or an unrecognized/empty answer): fall through and paste as usual
                 }
             }
-            for (key, value) in favorites {
-                data.entry(key.parse().unwrap())
-                    .or_insert_with(|| value.as_str().unwrap().to_string());
-            }
 
-            let input = data.keys().cloned().collect::<Vec<_>>().join("\n");
-            let command_name = launcher.unwrap()[0].as_str().unwrap();
-            let mut command = Command::new(command_name);
-            for arg in &launcher.unwrap()[1..] {
-                command.arg(arg.as_str().unwrap());
+            let mut sources = Vec::new();
+            let dir_path = cache_dir.join(&id);
+            if let Ok(mut read_dir) = fs::read_dir(dir_path).await {
+                while let Ok(Some(entry)) = read_dir.next_entry().await {
+                    let path = entry.path();
+                    if let Some(file_name) = path.file_name() {
+                        let file_name = file_name.to_string_lossy();
+                        if file_name.starts_with('.') {
+                            continue; // .mtime, .thumbnail.png: not a mime representation
+                        }
+                        let mime_type = file_name.replacen(".", "/", 1);
+                        if let Ok(contents) = fs::read(&path).await {
+                            sources.push((mime_type, contents));
+                        }
+                    }
+                }
             }
 
-            let mut child = command
-                .stdin(std::process::Stdio::piped())
-                .stdout(std::process::Stdio::piped())
-                .spawn()
-                .unwrap_or_else(|_| panic!("Cannot start your launcher, please confirm you have {} installed or configure another one", command_name));
+            let text_source = sources
+                .iter()
+                .find(|(mime, _)| mime.starts_with("text/") || mime == "UTF8_STRING")
+                .or_else(|| sources.first());
 
-            if let Some(mut stdin) = child.stdin.take() {
-                stdin.write_all(input.as_bytes()).await.unwrap();
+            if args.osc52 && text_source.is_some_and(|(_, contents)| send_osc52(contents, osc52_max_size)) {
+                return;
             }
 
-            let output = child.wait_with_output().await.unwrap();
-
-            let mut result = String::from_utf8_lossy(&output.stdout).into_owned();
-            result.pop(); // Remove trailing new line
-            if result.len() > 0 {
+            if on_wayland {
+                // wl-clipboard-rs can offer every mime type at once; keep using it natively here.
                 let mut opts = Options::new();
                 opts.foreground(true); // We need to keep the process alive for pasting to work
-                if favorites.contains_key(&result) {
-                    opts.copy(
-                        Source::Bytes(
-                            data.get(&result)
-                                .unwrap()
-                                .to_string()
-                                .into_bytes()
-                                .into_boxed_slice(),
-                        ),
-                        MimeType::Autodetect,
-                    )
-                    .expect("Failed to copy to clipboard");
-                } else {
-                    let prefix = data.get(&result).unwrap().as_str();
-                    let mut sources = Vec::new();
-                    let dir_path = format!("{}{}", cache_dir.to_str().unwrap(), prefix);
-                    if let Ok(mut read_dir) = fs::read_dir(dir_path).await {
-                        while let Ok(Some(entry)) = read_dir.next_entry().await {
-                            let path = entry.path();
-                            if let Some(file_name) = path.file_name() {
-                                let mime_type = file_name
-                                    .to_string_lossy()
-                                    .to_string()
-                                    .replacen(".", "/", 1);
-                                if let Ok(contents) = fs::read(&path).await {
-                                    sources.push(MimeSource {
-                                        source: Source::Bytes(contents.into()),
-                                        mime_type: MimeType::Specific(mime_type),
-                                    });
-                                }
-                            }
-                        }
-                    }
-
-                    if !sources.is_empty() {
-                        opts.copy_multi(sources)
-                            .expect("Failed to copy to clipboard");
-                    }
+                let multi_sources = sources
+                    .into_iter()
+                    .map(|(mime_type, contents)| MimeSource {
+                        source: Source::Bytes(contents.into()),
+                        mime_type: MimeType::Specific(mime_type),
+                    })
+                    .collect::<Vec<_>>();
+                if !multi_sources.is_empty() {
+                    opts.copy_multi(multi_sources)
+                        .expect("Failed to copy to clipboard");
                 }
+            } else if let Some((_, contents)) = text_source {
+                provider
+                    .set_contents(contents.clone(), Selection::Clipboard)
+                    .await
+                    .unwrap_or_else(|err| {
+                        panic!("Failed to copy to clipboard via {}: {}", provider.name(), err)
+                    });
             }
         }
     }
 }
 
+// Returns the line the user picked, or None if they dismissed the launcher.
+async fn run_launcher(launcher: &[Value], input: &str) -> Option<String> {
+    let command_name = launcher[0].as_str().unwrap();
+    let mut command = Command::new(command_name);
+    for arg in &launcher[1..] {
+        command.arg(arg.as_str().unwrap());
+    }
+
+    let mut child = command
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|_| panic!("Cannot start your launcher, please confirm you have {} installed or configure another one", command_name));
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes()).await.unwrap();
+    }
+
+    let output = child.wait_with_output().await.unwrap();
+
+    let mut result = String::from_utf8_lossy(&output.stdout).into_owned();
+    result.pop(); // Remove trailing new line
+    if result.len() > 0 {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+fn send_osc52(bytes: &[u8], max_size: usize) -> bool {
+    match osc52::set_clipboard(bytes, max_size) {
+        Ok(sent) => sent,
+        Err(err) => {
+            eprintln!("OSC52 write failed: {}", err);
+            false
+        }
+    }
+}
+
+// Used wherever there's no event-driven clipboard API to hook into (X11
+// tools, remote sessions).
+async fn poll_clipboard(
+    provider: Arc<dyn ClipboardProvider>,
+    selection: Selection,
+    cache_dir: PathBuf,
+    history_size: usize,
+) {
+    let mut last_seen: Option<Vec<u8>> = None;
+    loop {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let contents = match provider.get_contents(selection).await {
+            Ok(contents) if !contents.is_empty() => contents,
+            Ok(_) => continue,
+            Err(err) => {
+                eprintln!("Clipboard {:?} warning via {}: {}", selection, provider.name(), err);
+                continue;
+            }
+        };
+        if last_seen.as_ref() == Some(&contents) {
+            continue;
+        }
+        last_seen = Some(contents.clone());
+
+        if let Err(err) = history::store_event(&cache_dir, vec![("text/plain".to_string(), contents)]).await {
+            eprintln!("Failed to store clipboard entry: {}", err);
+        }
+
+        history::clean_history(&cache_dir, history_size).await.unwrap();
+    }
+}
+
 async fn listen_to_clipboard(paste_type: &str, cache_dir: PathBuf, history_size: usize) {
     let mut stream = WlClipboardPasteStream::init(match paste_type {
         "primary" => WlListenType::ListenOnSelect,
@@ -229,10 +394,7 @@ async fn listen_to_clipboard(paste_type: &str, cache_dir: PathBuf, history_size:
     .unwrap();
 
     for context in stream.paste_stream().flatten().flatten() {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
+        let mut mimes = Vec::new();
         for mime in context.mime_types {
             match get_contents(
                 match paste_type {
@@ -243,23 +405,21 @@ async fn listen_to_clipboard(paste_type: &str, cache_dir: PathBuf, history_size:
                 wl_clipboard_rs::paste::MimeType::Specific(&mime),
             ) {
                 Ok((mut reader, _)) => {
-                    let path = format!("{}{}", cache_dir.to_str().unwrap(), timestamp);
-                    fs::create_dir_all(Path::new(&path)).await.unwrap();
-                    let file_path = format!("{}/{}", &path, mime.replace("/", "."));
-                    let file_path_clone = file_path.clone();
-                    let copy_result = task::spawn_blocking(move || -> std::io::Result<u64> {
-                        let mut file = std::fs::File::create(&file_path_clone)?;
-                        std::io::copy(&mut reader, &mut file)
+                    let mime_clone = mime.clone();
+                    let read_result = task::spawn_blocking(move || -> std::io::Result<Vec<u8>> {
+                        let mut buf = Vec::new();
+                        reader.read_to_end(&mut buf)?;
+                        Ok(buf)
                     })
                     .await;
 
-                    match copy_result {
-                        Ok(Ok(_)) => (), // Success
+                    match read_result {
+                        Ok(Ok(bytes)) => mimes.push((mime_clone, bytes)),
                         Ok(Err(io_err)) => {
-                            eprintln!("Failed to copy content to {}: {}", file_path, io_err);
+                            eprintln!("Failed to read clipboard content for {}: {}", mime_clone, io_err);
                         }
                         Err(join_err) => {
-                            eprintln!("Blocking task for copy failed: {}", join_err);
+                            eprintln!("Blocking task for read failed: {}", join_err);
                         }
                     }
                 }
@@ -269,33 +429,12 @@ async fn listen_to_clipboard(paste_type: &str, cache_dir: PathBuf, history_size:
                 ),
             }
         }
-        clean_history(&cache_dir, history_size).await.unwrap();
-    }
-}
 
-async fn clean_history(directory: &Path, max: usize) -> io::Result<()> {
-    let mut entries = vec![];
-    if let Ok(mut read_dir) = fs::read_dir(directory).await {
-        while let Ok(Some(entry)) = read_dir.next_entry().await {
-            entries.push(entry);
-        }
-    }
-
-    entries.sort_by(|a, b| b.file_name().cmp(&a.file_name()));
-
-    for (index, entry) in entries.into_iter().enumerate() {
-        if index > max {
-            let path = entry.path();
-            if path.is_dir()
-                && !path
-                    .file_name()
-                    .unwrap_or_default()
-                    .to_string_lossy()
-                    .starts_with('.')
-            {
-                fs::remove_dir_all(&path).await?;
+        if !mimes.is_empty() {
+            if let Err(err) = history::store_event(&cache_dir, mimes).await {
+                eprintln!("Failed to store clipboard entry: {}", err);
             }
         }
+        history::clean_history(&cache_dir, history_size).await.unwrap();
     }
-    Ok(())
 }
@@ -0,0 +1,30 @@
+use std::io;
+use std::path::Path;
+use tokio::fs;
+
+// Refuses to touch dot-directories, same guard history::clean_history uses.
+pub async fn delete_entry(cache_dir: &Path, id: &str) -> io::Result<()> {
+    if id.starts_with('.') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "refusing to remove a dot-directory",
+        ));
+    }
+    fs::remove_dir_all(cache_dir.join(id)).await
+}
+
+// Uses toml_edit rather than toml so a hand-edited config.toml keeps its
+// comments and formatting.
+pub async fn pin_favorite(config_path: &Path, key: &str, text: &str) -> io::Result<()> {
+    let existing = fs::read_to_string(config_path).await.unwrap_or_default();
+    let mut doc = existing
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    if doc.get("favorites").is_none() {
+        doc["favorites"] = toml_edit::table();
+    }
+    doc["favorites"][key] = toml_edit::value(text);
+
+    fs::write(config_path, doc.to_string()).await
+}
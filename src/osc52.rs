@@ -0,0 +1,64 @@
+use base64::Engine;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+
+pub const DEFAULT_MAX_SIZE: usize = 100_000;
+
+// GNU screen's DCS strings top out well under 1 KiB; chunk the sequence to stay under that.
+const SCREEN_CHUNK_SIZE: usize = 76;
+
+// Writes to /dev/tty rather than stdout, since clapboard is often invoked
+// from a wrapper or keybinding where stdout isn't the controlling terminal.
+// Returns Ok(false) without writing anything if there's no tty to open or
+// the payload is over max_size, so callers can fall back to a normal copy.
+pub fn set_clipboard(bytes: &[u8], max_size: usize) -> io::Result<bool> {
+    let mut tty = match OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(tty) => tty,
+        Err(_) => return Ok(false),
+    };
+
+    let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+    if encoded.len() > max_size {
+        eprintln!(
+            "OSC52 payload ({} bytes) exceeds the configured cap ({} bytes); not sending",
+            encoded.len(),
+            max_size
+        );
+        return Ok(false);
+    }
+
+    let sequence = format!("\x1b]52;c;{encoded}\x07");
+    let sequence = if std::env::var_os("TMUX").is_some() {
+        wrap_tmux(&sequence)
+    } else if is_screen() {
+        wrap_screen(&sequence)
+    } else {
+        sequence
+    };
+
+    tty.write_all(sequence.as_bytes())?;
+    tty.flush()?;
+    Ok(true)
+}
+
+// tmux swallows escapes meant for the terminal underneath unless wrapped in
+// its own passthrough sequence, which also requires doubling ESC bytes.
+fn wrap_tmux(sequence: &str) -> String {
+    format!("\x1bPtmux;\x1b{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+}
+
+// Same idea as tmux, but screen also caps each escape sequence's length, so
+// long payloads are split into chunks wrapped and sent individually.
+fn wrap_screen(sequence: &str) -> String {
+    sequence
+        .as_bytes()
+        .chunks(SCREEN_CHUNK_SIZE)
+        .map(|chunk| format!("\x1bP{}\x1b\\", String::from_utf8_lossy(chunk)))
+        .collect()
+}
+
+fn is_screen() -> bool {
+    std::env::var("TERM")
+        .map(|term| term.starts_with("screen"))
+        .unwrap_or(false)
+}
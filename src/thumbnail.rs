@@ -0,0 +1,65 @@
+use image::GenericImageView;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::task;
+
+const THUMBNAIL_FILE: &str = ".thumbnail.png";
+const THUMBNAIL_SIZE: u32 = 128;
+
+// Describes an image entry ("🖼 image/png 1920x1080 · 34.0 KiB") and caches a
+// small preview PNG next to it. None if dir doesn't hold an image.
+pub async fn describe_and_cache(dir: &Path) -> Option<(String, PathBuf)> {
+    let (mime, path) = find_image(dir).await?;
+    let size = fs::metadata(&path).await.ok()?.len() as usize;
+
+    let thumb_path = dir.join(THUMBNAIL_FILE);
+    let have_thumb = fs::metadata(&thumb_path).await.is_ok();
+    let path_clone = path.clone();
+    let thumb_path_clone = thumb_path.clone();
+    let (width, height) = task::spawn_blocking(move || {
+        if have_thumb {
+            // Cached already: a header probe gets the dimensions without
+            // paying for a full decode of an image we're not re-saving.
+            return image::image_dimensions(&path_clone).ok();
+        }
+        let img = image::open(&path_clone).ok()?;
+        let _ = img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).save(&thumb_path_clone);
+        Some(img.dimensions())
+    })
+    .await
+    .ok()??;
+
+    Some((
+        format!("🖼 {mime} {width}x{height} · {}", human_size(size)),
+        thumb_path,
+    ))
+}
+
+async fn find_image(dir: &Path) -> Option<(String, PathBuf)> {
+    let mut read_dir = fs::read_dir(dir).await.ok()?;
+    while let Ok(Some(entry)) = read_dir.next_entry().await {
+        let path = entry.path();
+        if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
+            let mime = file_name.replacen('.', "/", 1);
+            if mime.starts_with("image/") {
+                return Some((mime, path));
+            }
+        }
+    }
+    None
+}
+
+fn human_size(bytes: usize) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} {}", UNITS[unit])
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}